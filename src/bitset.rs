@@ -0,0 +1,106 @@
+//! A [`TraitSet`] backed by a bitmap over interned trait indices, for a
+//! constant-factor speedup on union and subsumption over large rule sets
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::trait_set::TraitSet;
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+/// Assigns each distinct trait name a small, stable index
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<String, usize>,
+    names: Vec<String>,
+}
+
+impl Interner {
+    fn intern(&mut self, trait_: &str) -> usize {
+        if let Some(&id) = self.ids.get(trait_) {
+            return id;
+        }
+
+        let id = self.names.len();
+        self.names.push(trait_.to_owned());
+        self.ids.insert(trait_.to_owned(), id);
+        id
+    }
+
+    fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+}
+
+const BITS: usize = u64::BITS as usize;
+
+/// A set of trait names stored as a bitmap over interned indices instead of
+/// a `HashSet<String>`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitSetTraits {
+    words: Vec<u64>,
+}
+
+impl TraitSet for BitSetTraits {
+    fn insert(&mut self, trait_: String) {
+        let id = INTERNER.with(|interner| interner.borrow_mut().intern(&trait_));
+        let (word, bit) = (id / BITS, id % BITS);
+
+        if self.words.len() <= word {
+            self.words.resize(word + 1, 0);
+        }
+
+        self.words[word] |= 1 << bit;
+    }
+
+    fn contains(&self, trait_: &str) -> bool {
+        let Some(id) = INTERNER.with(|interner| interner.borrow().ids.get(trait_).copied()) else {
+            return false;
+        };
+
+        let (word, bit) = (id / BITS, id % BITS);
+
+        self.words.get(word).is_some_and(|bits| bits & (1 << bit) != 0)
+    }
+
+    fn len(&self) -> usize {
+        self.words.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    fn names(&self) -> Vec<String> {
+        INTERNER.with(|interner| {
+            let interner = interner.borrow();
+
+            self.words
+                .iter()
+                .enumerate()
+                .flat_map(|(word, &bits)| {
+                    (0..BITS)
+                        .filter(move |bit| bits & (1 << bit) != 0)
+                        .map(move |bit| word * BITS + bit)
+                })
+                .map(|id| interner.name(id).to_owned())
+                .collect()
+        })
+    }
+
+    fn union(&mut self, other: &Self) {
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+
+        for (word, bits) in self.words.iter_mut().zip(&other.words) {
+            *word |= bits;
+        }
+    }
+
+    fn subsumes(&self, other: &Self) -> bool {
+        other
+            .words
+            .iter()
+            .enumerate()
+            .all(|(i, &bits)| bits & !self.words.get(i).copied().unwrap_or(0) == 0)
+    }
+}
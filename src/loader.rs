@@ -0,0 +1,286 @@
+//! Composes relation files via `%include` and `%unset` directives on top of
+//! the grammar and `define` macro expansion from [`crate::parser`]
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+};
+
+use crate::parser::{self, Line, ParseError};
+use crate::trait_set::TraitSet;
+use crate::{Relation, Traits};
+
+/// Everything that can go wrong loading a relation file tree
+#[derive(Debug)]
+pub enum LoadError {
+    /// A line didn't match the grammar
+    Parse(ParseError),
+    /// Reading from stdin or an included file failed
+    Io(io::Error),
+    /// A `define` macro (directly or transitively) referenced itself
+    DefineCycle(String),
+    /// An `%include` chain looped back on a file already being loaded
+    IncludeCycle(PathBuf),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "{err}"),
+            Self::Io(err) => write!(f, "{err}"),
+            Self::DefineCycle(name) => write!(f, "define {name} is defined in terms of itself"),
+            Self::IncludeCycle(path) => {
+                write!(f, "%include cycle detected at {}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<ParseError> for LoadError {
+    fn from(err: ParseError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// The state accumulated while loading a file and everything it includes
+#[derive(Default)]
+struct Loaded {
+    defines: HashMap<String, Traits>,
+    /// Each define's right-hand side exactly as written, before expansion,
+    /// so a later define can be checked for transitively referencing itself
+    define_refs: HashMap<String, HashSet<String>>,
+    relations: Vec<Relation>,
+}
+
+/// Read the relations declared on stdin
+///
+/// The first line is an integer giving the number of top-level lines to
+/// read. Each line is a relation, a `define`, a `%unset`, or an `%include`
+/// that splices in another file's lines, resolved relative to the current
+/// directory
+pub fn load_stdin() -> Result<Vec<Relation>, LoadError> {
+    let stdin = io::stdin();
+    let mut lock = stdin.lock();
+
+    let mut buffer = String::new();
+    lock.read_line(&mut buffer)?;
+
+    let count: usize = buffer.trim().parse().map_err(|_| {
+        LoadError::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "expected a relation count",
+        ))
+    })?;
+
+    let mut loaded = Loaded::default();
+    let dir = std::env::current_dir()?;
+    let mut stack = Vec::new();
+
+    for _ in 0..count {
+        buffer.clear();
+        lock.read_line(&mut buffer)?;
+        load_line(buffer.trim(), &dir, &mut stack, &mut loaded)?;
+    }
+
+    Ok(loaded.relations)
+}
+
+/// Load every line of `path`, splicing the result into `loaded`
+fn load_file(path: &Path, stack: &mut Vec<PathBuf>, loaded: &mut Loaded) -> Result<(), LoadError> {
+    let canonical = path.canonicalize()?;
+
+    if stack.contains(&canonical) {
+        return Err(LoadError::IncludeCycle(path.to_owned()));
+    }
+
+    let file = fs::File::open(path)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(".")).to_owned();
+
+    stack.push(canonical);
+
+    for line in io::BufReader::new(file).lines() {
+        load_line(line?.trim(), &dir, stack, loaded)?;
+    }
+
+    stack.pop();
+
+    Ok(())
+}
+
+/// Parse and apply a single line, recursing into `%include`d files relative
+/// to `dir`
+fn load_line(
+    line: &str,
+    dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    loaded: &mut Loaded,
+) -> Result<(), LoadError> {
+    if line.is_empty() {
+        return Ok(());
+    }
+
+    match parser::parse_line(line)? {
+        Line::Define { name, traits } => {
+            let raw_refs: HashSet<String> = traits.names().into_iter().collect();
+            let mut seen = HashSet::new();
+
+            if raw_refs
+                .iter()
+                .any(|r| references(&loaded.define_refs, r, &name, &mut seen))
+            {
+                return Err(LoadError::DefineCycle(name));
+            }
+
+            let expanded = expand(traits, &loaded.defines);
+            loaded.define_refs.insert(name.clone(), raw_refs);
+            loaded.defines.insert(name, expanded);
+        }
+        Line::Relation(relation) => {
+            let from = expand(relation.from, &loaded.defines);
+            let to = expand(relation.to, &loaded.defines);
+
+            loaded.relations.push(Relation::new(from, to));
+        }
+        Line::Unset(relation) => {
+            let from = expand(relation.from, &loaded.defines);
+            let to = expand(relation.to, &loaded.defines);
+
+            loaded.relations.retain(|r| r.from != from || r.to != to);
+        }
+        Line::Include(path) => load_file(&dir.join(path), stack, loaded)?,
+    }
+
+    Ok(())
+}
+
+/// Whether `target` is reachable from `name` by following raw, pre-
+/// expansion define references, used to reject a define that (directly or
+/// transitively) references itself
+fn references(
+    define_refs: &HashMap<String, HashSet<String>>,
+    name: &str,
+    target: &str,
+    seen: &mut HashSet<String>,
+) -> bool {
+    if name == target {
+        return true;
+    }
+
+    if !seen.insert(name.to_owned()) {
+        return false;
+    }
+
+    define_refs.get(name).is_some_and(|refs| {
+        refs.iter()
+            .any(|r| references(define_refs, r, target, seen))
+    })
+}
+
+/// Substitute any `define`d macro names in `traits` with their underlying
+/// traits
+///
+/// Defines are resolved to their final trait set as soon as they're read, so
+/// a single substitution pass is enough to also cover a define that itself
+/// references an earlier define
+fn expand(traits: Traits, defines: &HashMap<String, Traits>) -> Traits {
+    let mut expanded = Traits::default();
+
+    for trait_ in traits.names() {
+        match defines.get(&trait_) {
+            Some(resolved) => TraitSet::union(&mut expanded, resolved),
+            None => TraitSet::insert(&mut expanded, trait_),
+        }
+    }
+
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `lines` through [`load_line`] in order, as `load_stdin` would,
+    /// and return the relations left standing
+    fn load_lines(lines: &[&str], dir: &Path) -> Result<Vec<Relation>, LoadError> {
+        let mut loaded = Loaded::default();
+        let mut stack = Vec::new();
+
+        for line in lines {
+            load_line(line, dir, &mut stack, &mut loaded)?;
+        }
+
+        Ok(loaded.relations)
+    }
+
+    #[test]
+    fn direct_define_cycle_errors() {
+        let dir = std::env::current_dir().unwrap();
+        let err = load_lines(&["define A = A and B"], &dir).unwrap_err();
+
+        assert!(matches!(err, LoadError::DefineCycle(name) if name == "A"));
+    }
+
+    #[test]
+    fn transitive_define_cycle_errors() {
+        let dir = std::env::current_dir().unwrap();
+        let err = load_lines(&["define A = B", "define B = C", "define C = A"], &dir).unwrap_err();
+
+        assert!(matches!(err, LoadError::DefineCycle(name) if name == "C"));
+    }
+
+    #[test]
+    fn a_define_may_still_reference_an_earlier_define_twice_removed() {
+        let dir = std::env::current_dir().unwrap();
+        let relations = load_lines(
+            &[
+                "define WINGED = FEATHERS",
+                "define BIRDS = WINGED",
+                "PIGS that are BIRDS can FLY",
+            ],
+            &dir,
+        )
+        .unwrap();
+
+        assert_eq!(relations.len(), 1);
+        assert!(TraitSet::contains(&relations[0].from, "FEATHERS"));
+    }
+
+    #[test]
+    fn unset_removes_a_matching_relation_and_nothing_else() {
+        let dir = std::env::current_dir().unwrap();
+        let relations = load_lines(
+            &["PIGS can FLY", "PIGS can SWIM", "%unset PIGS can FLY"],
+            &dir,
+        )
+        .unwrap();
+
+        assert_eq!(relations.len(), 1);
+        assert!(TraitSet::contains(&relations[0].to, "SWIM"));
+    }
+
+    #[test]
+    fn include_cycle_errors() {
+        let dir = std::env::temp_dir().join(format!("wpf-include-cycle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "%include b.txt\n").unwrap();
+        fs::write(dir.join("b.txt"), "%include a.txt\n").unwrap();
+
+        let mut loaded = Loaded::default();
+        let mut stack = Vec::new();
+        let err = load_file(&dir.join("a.txt"), &mut stack, &mut loaded).unwrap_err();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(err, LoadError::IncludeCycle(_)));
+    }
+}
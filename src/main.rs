@@ -1,190 +1,450 @@
+#[cfg(not(feature = "bitset"))]
+use std::collections::HashSet;
 use std::{
-    cell::RefCell,
-    collections::HashSet,
-    io::{self, BufRead, StdinLock},
-    time::Instant,
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead},
 };
 
+use clap::Parser;
+
+use crate::trait_set::TraitSet;
+
+#[cfg(feature = "bitset")]
+mod bitset;
+mod loader;
+mod parser;
+mod trait_set;
+
+#[cfg(feature = "bitset")]
+type Traits = bitset::BitSetTraits;
+#[cfg(not(feature = "bitset"))]
+type Traits = HashSet<String>;
+
 /// A relation between an object with traits and abilities and another object with traits and abilities
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Relation {
-    from: HashSet<String>,
-    to: RefCell<HashSet<String>>,
+    pub(crate) from: Traits,
+    pub(crate) to: Traits,
 }
 
 impl Relation {
     /// Create a new relation from it's raw fields
-    fn new(from: HashSet<String>, to: HashSet<String>) -> Self {
-        Self {
-            from,
-            to: RefCell::new(to),
-        }
+    fn new(from: Traits, to: Traits) -> Self {
+        Self { from, to }
     }
+}
 
-    /// Check if `self.to` and `other.from` match
-    fn cascades(&self, other: &Self) -> bool {
-        let matching = self.to.borrow().intersection(&other.from).count();
+/// Ask whether a set of traits lets you reach another, via chains of
+/// `X that can Y are/have/can Z`-style relations
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// Trait(s) to seed the query from
+    #[arg(long, default_value = "PIGS")]
+    from: Vec<String>,
+
+    /// Trait(s) to check reachability of
+    #[arg(long, default_value = "FLY")]
+    to: Vec<String>,
+
+    /// Require every object with the seed traits (and nothing else assumed)
+    /// to reach the target, rather than just some object that happens to
+    /// have both
+    #[arg(long, conflicts_with = "some")]
+    all: bool,
+
+    /// Accept a target reachable by some object that also has the seed
+    /// traits, even if it needed other traits to get there
+    #[arg(long, conflicts_with = "all")]
+    some: bool,
+
+    /// Read `FROM -> TO` queries, one per line, from the rest of stdin after
+    /// the relations and answer each in turn, in place of the single
+    /// `--from`/`--to` query
+    #[arg(long)]
+    batch: bool,
+}
 
-        other.from.len() == matching
-    }
+fn main() {
+    let cli = Cli::parse();
 
-    /// Check if `self.from` and `other.from` match
-    fn matches(&self, other: &Self) -> bool {
-        self.from.intersection(&other.from).count() == self.from.len()
-    }
+    let relations = match loader::load_stdin() {
+        Ok(relations) => relations,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
 
-    /// Add all items in `other.to` to `self.to`
-    ///
-    /// Returns `true` if `self.to` actually changed
-    fn extend(&self, other: &Self) -> bool {
-        let length = self.to.borrow().len();
+    if cli.batch {
+        let stdin = io::stdin();
 
-        self.to.borrow_mut().extend(other.to.borrow().clone());
+        for line in stdin.lock().lines() {
+            let line = line.expect("stdin is readable");
+            let line = line.trim();
 
-        length < self.to.borrow().len()
+            if line.is_empty() {
+                continue;
+            }
+
+            match parse_query(line) {
+                Some((from, to)) => println!("{}", verdict(&relations, &from, &to, &cli)),
+                None => eprintln!("malformed query, expected `FROM -> TO`: {line}"),
+            }
+        }
+    } else {
+        let from = to_traits(cli.from.iter().cloned());
+        let to = to_traits(cli.to.iter().cloned());
+
+        println!("{}", verdict(&relations, &from, &to, &cli));
     }
+}
+
+/// Collect trait names into a [`Traits`] set
+pub(crate) fn to_traits(names: impl IntoIterator<Item = String>) -> Traits {
+    let mut traits = Traits::default();
 
-    /// Check if this relation concludes pigs can fly
-    ///
-    /// `all` specifies if all pigs should be able to fly, or just some
-    fn can_fly(&self, all: bool) -> bool {
-        (self.from.contains("PIGS") && self.to.borrow().contains("FLY"))
-            || (!all && self.to.borrow().contains("PIGS") && self.to.borrow().contains("FLY"))
+    for name in names {
+        traits.insert(name);
     }
+
+    traits
 }
 
-fn main() {
-    let instant = Instant::now();
+/// Parse a batch query line of the form `FROM_TRAIT... -> TO_TRAIT...`
+fn parse_query(line: &str) -> Option<(Traits, Traits)> {
+    let (from, to) = line.split_once("->")?;
 
-    let relations = read_relations();
+    let from = to_traits(from.split_whitespace().map(str::to_owned));
+    let to = to_traits(to.split_whitespace().map(str::to_owned));
 
-    dbg!(instant.elapsed());
+    Some((from, to))
+}
+
+/// Describe whether `to` is reachable from `from`, under whichever of
+/// `--all`/`--some` was requested (or the full tri-state check, if neither
+/// was passed)
+fn verdict(relations: &[Relation], from: &Traits, to: &Traits, cli: &Cli) -> String {
+    let from_label = join(from);
+    let to_label = join(to);
+
+    if cli.some {
+        return if query(relations, from, to, false) {
+            format!("Some {from_label} can {to_label}")
+        } else {
+            format!("No {from_label} can {to_label}")
+        };
+    }
 
-    if can_fly(relations.clone(), true) {
-        println!("All pigs can fly");
-    } else if can_fly(relations, false) {
-        println!("Some pigs can fly");
+    if query(relations, from, to, true) {
+        format!("All {from_label} can {to_label}")
+    } else if cli.all {
+        format!("No {from_label} can {to_label}")
+    } else if query(relations, from, to, false) {
+        format!("Some {from_label} can {to_label}")
     } else {
-        println!("No pigs can fly");
+        format!("No {from_label} can {to_label}")
     }
+}
 
-    dbg!(instant.elapsed());
+/// Join a trait set into a human-readable, `and`-separated list
+fn join(traits: &Traits) -> String {
+    let mut names = traits.names();
+    names.sort_unstable();
+    names.join(" and ")
 }
 
-/// Check if a collection of relations allow pigs to fly
+/// Ask whether `to` is reachable from `from` over `relations`
 ///
-/// `all` specifies if all pigs should be able to fly, or just some
-fn can_fly(relations: Vec<Relation>, all: bool) -> bool {
-    let mut changed = true;
+/// `all` requires every object with exactly the seed traits to reach the
+/// target, i.e. that `to` lie in the forward closure of `from` alone. `some`
+/// is satisfied by that too, but also by any other object — one seeded by
+/// some unrelated relation's own `from` set — whose closure happens to
+/// contain both the seed traits and the target, since such an object
+/// witnesses that *some* thing with the seed traits reaches the target even
+/// though not every such thing needs to
+fn query(relations: &[Relation], from: &Traits, to: &Traits, all: bool) -> bool {
+    let reached = closure(relations, from);
+
+    if all {
+        return reached.subsumes(to);
+    }
 
-    for relation_a in &relations {
-        for relation_b in &relations {
-            if std::ptr::eq(relation_a, relation_b) {
-                continue;
-            }
+    reached.subsumes(to) || witnessed(relations, from, to)
+}
 
-            if relation_a.matches(relation_b) && relation_b.extend(relation_a) {
-                changed = true;
-            }
+/// Forward-chain from `seed`, visiting only the relations that could newly
+/// fire instead of rescanning every pair on every pass, and return every
+/// trait reachable (including `seed` itself)
+fn closure(relations: &[Relation], seed: &Traits) -> Traits {
+    // Map each trait to the relations whose `from` set contains it, and track
+    // how many `from` members of each relation are still undetermined
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut remaining: Vec<usize> = Vec::with_capacity(relations.len());
+
+    for (i, relation) in relations.iter().enumerate() {
+        remaining.push(TraitSet::len(&relation.from));
+
+        for trait_ in relation.from.names() {
+            index.entry(trait_).or_default().push(i);
         }
     }
 
-    while changed {
-        changed = false;
+    let mut held = Traits::default();
+    let mut queue: VecDeque<String> = VecDeque::new();
 
-        for relation_a in &relations {
-            for relation_b in &relations {
-                if std::ptr::eq(relation_a, relation_b) {
+    for trait_ in seed.names() {
+        TraitSet::insert(&mut held, trait_.clone());
+        queue.push_back(trait_);
+    }
+
+    while let Some(trait_) = queue.pop_front() {
+        let Some(candidates) = index.get(&trait_) else {
+            continue;
+        };
+
+        for &i in candidates {
+            remaining[i] -= 1;
+
+            if remaining[i] != 0 {
+                continue;
+            }
+
+            for t in relations[i].to.names() {
+                if TraitSet::contains(&held, &t) {
                     continue;
                 }
 
-                if relation_a.cascades(relation_b) && relation_a.extend(relation_b) {
-                    changed = true;
-                }
+                TraitSet::insert(&mut held, t.clone());
+                queue.push_back(t);
             }
         }
+    }
 
-        for relation in &relations {
-            if relation.can_fly(all) {
-                return true;
-            }
+    held
+}
+
+/// Number of `u64` words needed to hold one bit per relation
+fn origin_words(relations: &[Relation]) -> usize {
+    relations.len().div_ceil(u64::BITS as usize)
+}
+
+/// Merge `bits` into the word-vector `reached` has on file for `trait_`,
+/// queuing the trait for another pass if that actually added any new origin
+fn mark_reached(
+    reached: &mut HashMap<String, Vec<u64>>,
+    queue: &mut VecDeque<String>,
+    width: usize,
+    trait_: &str,
+    bits: &[u64],
+) {
+    let slot = reached
+        .entry(trait_.to_owned())
+        .or_insert_with(|| vec![0; width]);
+    let mut changed = false;
+
+    for (word, new) in slot.iter_mut().zip(bits) {
+        let merged = *word | new;
+
+        if merged != *word {
+            changed = true;
         }
+
+        *word = merged;
     }
 
-    false
+    if changed {
+        queue.push_back(trait_.to_owned());
+    }
 }
 
-/// Read all the relations from stdin
+/// Whether some object other than the query's own seed — one seeded by a
+/// relation's own `from` set — has both `from` and `to` in its forward
+/// closure
 ///
-/// Should start with an integer with the amount of relations
-fn read_relations() -> Vec<Relation> {
-    let stdin = io::stdin();
-    let mut lock = stdin.lock();
+/// Testing each relation as a candidate seed by running a whole separate
+/// [`closure`] per relation is near-linear per relation but quadratic
+/// overall across all of them. Instead, fire every relation's `from` set as
+/// its own seed in one shared forward-chaining pass, tagging each seed with
+/// a bit for the relation it came from, so a trait's reachability from
+/// every candidate seed is tracked together rather than re-derived from
+/// scratch per candidate
+fn witnessed(relations: &[Relation], from: &Traits, to: &Traits) -> bool {
+    if relations.is_empty() {
+        return false;
+    }
 
-    let mut buffer = String::new();
-    lock.read_line(&mut buffer).unwrap();
-    let count: usize = buffer.trim().parse().unwrap();
+    let width = origin_words(relations);
 
-    let mut relations = Vec::with_capacity(count);
+    // Map each trait to the relations whose `from` set contains it, as in `closure`
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
 
-    for _ in 0..count {
-        buffer.clear();
-        relations.push(read_relation(&mut lock, &mut buffer))
+    for (i, relation) in relations.iter().enumerate() {
+        for trait_ in relation.from.names() {
+            index.entry(trait_).or_default().push(i);
+        }
     }
 
-    relations
-}
+    // For each trait, the relation-origins whose forward closure has reached
+    // it so far
+    let mut reached: HashMap<String, Vec<u64>> = HashMap::new();
+    // For each relation, the origins it has already fired for, so a later
+    // pass over it only contributes newly-reached origins
+    let mut fired: Vec<Vec<u64>> = vec![vec![0; width]; relations.len()];
+    let mut queue: VecDeque<String> = VecDeque::new();
+
+    for (i, relation) in relations.iter().enumerate() {
+        let mut origin = vec![0u64; width];
+        origin[i / u64::BITS as usize] |= 1 << (i % u64::BITS as usize);
+
+        for trait_ in relation.from.names() {
+            mark_reached(&mut reached, &mut queue, width, &trait_, &origin);
+        }
+    }
 
-/// Read a single relation from stdin
-fn read_relation(lock: &mut StdinLock, buffer: &mut String) -> Relation {
-    lock.read_line(buffer).unwrap();
+    while let Some(trait_) = queue.pop_front() {
+        let Some(candidates) = index.get(&trait_) else {
+            continue;
+        };
+
+        for &i in candidates {
+            let relation = &relations[i];
+
+            // The origins for which every member of this relation's `from`
+            // has now been reached
+            let mut and_mask = vec![!0u64; width];
+
+            for member in relation.from.names() {
+                match reached.get(&member) {
+                    Some(bits) => {
+                        for (word, bit) in and_mask.iter_mut().zip(bits) {
+                            *word &= bit;
+                        }
+                    }
+                    None => {
+                        and_mask = vec![0; width];
+                        break;
+                    }
+                }
+            }
 
-    let mut split = buffer.split_whitespace();
+            let new_bits: Vec<u64> = and_mask
+                .iter()
+                .zip(&fired[i])
+                .map(|(mask, already_fired)| mask & !already_fired)
+                .collect();
 
-    let from = parse_from(&mut split);
-    let to = parse_to(split);
+            if new_bits.iter().all(|&word| word == 0) {
+                continue;
+            }
 
-    Relation::new(from, to)
-}
+            for (word, new) in fired[i].iter_mut().zip(&new_bits) {
+                *word |= new;
+            }
 
-/// Parse the first part of a relation statement
-fn parse_from<'a>(split: &mut impl Iterator<Item = &'a str>) -> HashSet<String> {
-    let mut from = HashSet::new();
-
-    while let Some(value) = split.next() {
-        from.insert(value.to_owned());
-
-        match split.next() {
-            Some("with") | Some("and") => continue,
-            Some("that") => match split.next() {
-                Some("can") => continue,
-                _ => unreachable!(),
-            },
-            Some("are") | Some("have") | Some("can") => break,
-            _ => unreachable!(),
+            for trait_ in relation.to.names() {
+                mark_reached(&mut reached, &mut queue, width, &trait_, &new_bits);
+            }
         }
     }
 
-    from
-}
+    let mut witnesses = vec![!0u64; width];
 
-/// Parse the second part of a relation statement
-fn parse_to<'a>(mut split: impl Iterator<Item = &'a str>) -> HashSet<String> {
-    let mut to = HashSet::new();
-
-    while let Some(value) = split.next() {
-        to.insert(value.to_owned());
-
-        match split.next() {
-            Some("with") | Some("and") => continue,
-            Some("that") => match split.next() {
-                Some("can") => continue,
-                _ => unreachable!(),
-            },
-            None => break,
-            _ => unreachable!(),
+    for trait_ in from.names().into_iter().chain(to.names()) {
+        match reached.get(&trait_) {
+            Some(bits) => {
+                for (word, bit) in witnesses.iter_mut().zip(bits) {
+                    *word &= bit;
+                }
+            }
+            None => return false,
         }
     }
 
-    to
+    witnesses.iter().any(|&word| word != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relation(from: &[&str], to: &[&str]) -> Relation {
+        Relation::new(
+            to_traits(from.iter().map(|s| s.to_string())),
+            to_traits(to.iter().map(|s| s.to_string())),
+        )
+    }
+
+    fn traits(names: &[&str]) -> Traits {
+        to_traits(names.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn some_considers_objects_that_acquire_a_seed_trait_as_a_conclusion() {
+        // B are PIGS / B are FLY: a B-object is both a pig and a flyer, even
+        // though the closure of {PIGS} alone never reaches FLY
+        let relations = vec![relation(&["B"], &["PIGS"]), relation(&["B"], &["FLY"])];
+
+        assert!(!query(&relations, &traits(&["PIGS"]), &traits(&["FLY"]), true));
+        assert!(query(&relations, &traits(&["PIGS"]), &traits(&["FLY"]), false));
+    }
+
+    #[test]
+    fn redundantly_relisting_a_seed_trait_does_not_demote_all_to_some() {
+        let redundant = vec![
+            relation(&["PIGS"], &["BIRDS", "PIGS"]),
+            relation(&["BIRDS", "PIGS"], &["FLY"]),
+        ];
+        let plain = vec![
+            relation(&["PIGS"], &["BIRDS"]),
+            relation(&["BIRDS", "PIGS"], &["FLY"]),
+        ];
+
+        assert!(query(&redundant, &traits(&["PIGS"]), &traits(&["FLY"]), true));
+        assert!(query(&plain, &traits(&["PIGS"]), &traits(&["FLY"]), true));
+    }
+
+    #[test]
+    fn unrelated_relations_do_not_produce_a_false_positive() {
+        let relations = vec![
+            relation(&["PIGS"], &["A"]),
+            relation(&["A"], &["PIGS", "T"]),
+            relation(&["PIGS"], &["B"]),
+            relation(&["B"], &["T"]),
+            relation(&["T", "U"], &["FLY"]),
+        ];
+
+        assert!(!query(&relations, &traits(&["PIGS"]), &traits(&["FLY"]), true));
+        assert!(!query(&relations, &traits(&["PIGS"]), &traits(&["FLY"]), false));
+    }
+
+    #[test]
+    fn a_trait_derived_more_than_once_does_not_panic() {
+        let relations = vec![
+            relation(&["PIGS"], &["A"]),
+            relation(&["A"], &["PIGS", "T"]),
+            relation(&["PIGS"], &["B"]),
+            relation(&["B"], &["T"]),
+            relation(&["T"], &["FLY"]),
+        ];
+
+        assert!(query(&relations, &traits(&["PIGS"]), &traits(&["FLY"]), true));
+    }
+
+    #[test]
+    fn some_finds_a_witness_past_the_first_bitset_word() {
+        // 70 unrelated decoy relations push the real witness's origin past
+        // bit 64, into the second word of the per-relation origin bitset
+        let mut relations = Vec::new();
+
+        for i in 0..70 {
+            relations.push(relation(&[&format!("D{i}")], &[&format!("E{i}")]));
+        }
+
+        relations.push(relation(&["W"], &["PIGS"]));
+        relations.push(relation(&["W"], &["FLY"]));
+
+        assert!(!query(&relations, &traits(&["PIGS"]), &traits(&["FLY"]), true));
+        assert!(query(&relations, &traits(&["PIGS"]), &traits(&["FLY"]), false));
+    }
 }
@@ -0,0 +1,222 @@
+//! Grammar for relation statements, e.g. "PIGS with WINGS can FLY", and
+//! `define` macros, e.g. "define BIRDS = WINGS and FEATHERS"
+
+use std::fmt;
+
+use pest::Parser;
+use pest_derive::Parser;
+
+use crate::{to_traits, Relation, Traits};
+
+#[derive(Parser)]
+#[grammar = "relation.pest"]
+struct RelationParser;
+
+/// A statement that doesn't match the grammar
+#[derive(Debug)]
+pub struct ParseError(Box<pest::error::Error<Rule>>);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<pest::error::Error<Rule>> for ParseError {
+    fn from(err: pest::error::Error<Rule>) -> Self {
+        Self(Box::new(err))
+    }
+}
+
+/// A single parsed line of input
+pub enum Line {
+    /// A relation statement
+    Relation(Relation),
+    /// A `define NAME = trait_list` macro declaration
+    Define { name: String, traits: Traits },
+    /// A `%include PATH` directive splicing in another file's lines
+    Include(String),
+    /// A `%unset` directive removing a previously declared relation
+    Unset(Relation),
+}
+
+/// Parse a single relation statement directly, independently of `define`,
+/// `%include`, `%unset`, and stdin's line-count framing, so the grammar can
+/// be unit-tested on its own
+///
+/// This binary has no other caller for it yet; it exists for the tests below
+#[allow(dead_code)]
+pub fn parse_relation(input: &str) -> Result<Relation, ParseError> {
+    let line = RelationParser::parse(Rule::relation_line, input)?
+        .next()
+        .expect("relation_line rule always produces exactly one pair");
+
+    let statement = line
+        .into_inner()
+        .next()
+        .expect("relation_line always wraps a relation");
+
+    Ok(relation(statement))
+}
+
+/// Parse a single line of input into a relation, a `define` macro, or an
+/// `%include`/`%unset` directive
+pub fn parse_line(input: &str) -> Result<Line, ParseError> {
+    let line = RelationParser::parse(Rule::line, input)?
+        .next()
+        .expect("line rule always produces exactly one pair");
+
+    let statement = line
+        .into_inner()
+        .next()
+        .expect("line always wraps a define, include, unset or relation");
+
+    Ok(match statement.as_rule() {
+        Rule::define => {
+            let mut inner = statement.into_inner();
+
+            let name = inner
+                .find(|p| p.as_rule() == Rule::r#trait)
+                .expect("define always names a macro")
+                .as_str()
+                .to_owned();
+            let traits = trait_list(
+                inner
+                    .find(|p| p.as_rule() == Rule::trait_list)
+                    .expect("define always has a trait list"),
+            );
+
+            Line::Define { name, traits }
+        }
+        Rule::relation => Line::Relation(relation(statement)),
+        Rule::unset => Line::Unset(relation(statement)),
+        Rule::include => {
+            let path = statement
+                .into_inner()
+                .next()
+                .expect("include always names a path")
+                .as_str()
+                .to_owned();
+
+            Line::Include(path)
+        }
+        rule => unreachable!("line only ever wraps a known statement, got {rule:?}"),
+    })
+}
+
+/// Build a [`Relation`] out of a `relation` or `unset` pair, which share the
+/// same `trait_list ~ verb ~ trait_list` shape
+///
+/// The `verb` between the two lists is matched atomically (so its keyword
+/// boundary check isn't defeated by implicit whitespace skipping) and so
+/// shows up as a sibling pair here; it carries no information we need, so
+/// it's filtered out rather than addressed positionally
+fn relation(pair: pest::iterators::Pair<Rule>) -> Relation {
+    let mut lists = pair
+        .into_inner()
+        .filter(|p| p.as_rule() == Rule::trait_list)
+        .map(trait_list);
+
+    let from = lists.next().expect("relation always has a subject");
+    let to = lists.next().expect("relation always has an object");
+
+    Relation::new(from, to)
+}
+
+/// Collect the traits out of a `trait_list` pair
+///
+/// The `conjunction` separating each pair of traits is matched atomically
+/// for the same keyword-boundary reason as `verb`, so it's filtered out here
+/// too rather than relied on being absent
+fn trait_list(pair: pest::iterators::Pair<Rule>) -> Traits {
+    to_traits(
+        pair.into_inner()
+            .filter(|t| t.as_rule() == Rule::r#trait)
+            .map(|t| t.as_str().to_owned()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relation(from: &[&str], to: &[&str]) -> Relation {
+        Relation::new(
+            to_traits(from.iter().map(|s| s.to_string())),
+            to_traits(to.iter().map(|s| s.to_string())),
+        )
+    }
+
+    #[test]
+    fn parses_a_simple_relation() {
+        assert_eq!(
+            parse_relation("PIGS are FLY").unwrap(),
+            relation(&["PIGS"], &["FLY"])
+        );
+    }
+
+    #[test]
+    fn parses_with_and_conjunctions() {
+        assert_eq!(
+            parse_relation("PIGS with WINGS can FLY").unwrap(),
+            relation(&["PIGS", "WINGS"], &["FLY"])
+        );
+        assert_eq!(
+            parse_relation("PIGS with WINGS and FEATHERS can FLY").unwrap(),
+            relation(&["PIGS", "WINGS", "FEATHERS"], &["FLY"])
+        );
+    }
+
+    #[test]
+    fn parses_that_plus_any_verb_as_a_conjunction() {
+        assert_eq!(
+            parse_relation("PIGS that are BIRDS can FLY").unwrap(),
+            relation(&["PIGS", "BIRDS"], &["FLY"])
+        );
+        assert_eq!(
+            parse_relation("PIGS that have WINGS can FLY").unwrap(),
+            relation(&["PIGS", "WINGS"], &["FLY"])
+        );
+        assert_eq!(
+            parse_relation("PIGS that can OINK are FARM_ANIMALS with SNOUT").unwrap(),
+            relation(&["PIGS", "OINK"], &["FARM_ANIMALS", "SNOUT"])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(parse_relation("PIGS FLY").is_err());
+        assert!(parse_relation("").is_err());
+    }
+
+    #[test]
+    fn parse_line_recognizes_a_define() {
+        let Line::Define { name, traits } = parse_line("define BIRDS = WINGS and FEATHERS").unwrap()
+        else {
+            panic!("expected a define");
+        };
+
+        assert_eq!(name, "BIRDS");
+        assert_eq!(traits, to_traits(["WINGS", "FEATHERS"].map(String::from)));
+    }
+
+    #[test]
+    fn parse_line_recognizes_an_include() {
+        let Line::Include(path) = parse_line("%include other.txt").unwrap() else {
+            panic!("expected an include");
+        };
+
+        assert_eq!(path, "other.txt");
+    }
+
+    #[test]
+    fn parse_line_recognizes_an_unset() {
+        let Line::Unset(parsed) = parse_line("%unset PIGS are FLY").unwrap() else {
+            panic!("expected an unset");
+        };
+
+        assert_eq!(parsed, relation(&["PIGS"], &["FLY"]));
+    }
+}
@@ -0,0 +1,55 @@
+//! Decouples the inference engine from the concrete storage backing a
+//! [`Relation`](crate::Relation)'s trait sets
+
+use std::collections::HashSet;
+
+/// A set of trait names, supporting the membership, union and subsumption
+/// operations the loader and the inference engine use
+///
+/// Implemented once for [`HashSet<String>`] and, behind the `bitset`
+/// feature, for an interned bitmap representation with a much cheaper
+/// [`union`](TraitSet::union)/[`subsumes`](TraitSet::subsumes) on large rule
+/// sets
+pub trait TraitSet: Default {
+    /// Add a single trait to the set
+    fn insert(&mut self, trait_: String);
+
+    /// Whether the set contains `trait_`
+    fn contains(&self, trait_: &str) -> bool;
+
+    /// Number of traits in the set
+    fn len(&self) -> usize;
+
+    /// The traits in the set, in no particular order
+    fn names(&self) -> Vec<String>;
+
+    /// Merge every trait of `other` into `self`
+    fn union(&mut self, other: &Self);
+
+    /// Whether `self` contains every trait in `other`
+    fn subsumes(&self, other: &Self) -> bool {
+        other.names().iter().all(|t| self.contains(t))
+    }
+}
+
+impl TraitSet for HashSet<String> {
+    fn insert(&mut self, trait_: String) {
+        HashSet::insert(self, trait_);
+    }
+
+    fn contains(&self, trait_: &str) -> bool {
+        HashSet::contains(self, trait_)
+    }
+
+    fn len(&self) -> usize {
+        HashSet::len(self)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.iter().cloned().collect()
+    }
+
+    fn union(&mut self, other: &Self) {
+        Extend::extend(self, other.iter().cloned());
+    }
+}